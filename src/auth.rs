@@ -0,0 +1,198 @@
+//! Inbound request authentication and upstream credential injection for the proxy.
+//!
+//! Two independent concerns live here: deciding whether an inbound request to
+//! the proxy is allowed through (`AuthMode`), and attaching a bearer token to
+//! the outgoing request to LM Studio when the upstream itself is secured.
+
+use axum::http::{HeaderMap, StatusCode};
+use serde::{Deserialize, Serialize};
+use subtle::ConstantTimeEq;
+
+use crate::ServeConfig;
+
+/// How inbound requests to the proxy are authenticated.
+pub enum AuthMode {
+    /// No authentication required (the default).
+    Open,
+    /// A static bearer token must match exactly.
+    StaticToken(String),
+    /// A JWT signed with this HMAC secret must validate, carry `exp` and `iat` claims,
+    /// and not be expired (`iat` is required but its age is not checked).
+    Jwt(String),
+}
+
+impl AuthMode {
+    pub fn from_config(config: &ServeConfig) -> Self {
+        if let Some(secret) = &config.jwt_secret {
+            AuthMode::Jwt(secret.clone())
+        } else if let Some(token) = &config.require_api_key {
+            AuthMode::StaticToken(token.clone())
+        } else {
+            AuthMode::Open
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Claims {
+    exp: u64,
+    iat: u64,
+}
+
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(axum::http::header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+}
+
+/// Validates the inbound `Authorization` header against the configured auth mode.
+/// Returns `Ok(())` when the mode is `Open` or the credential checks out.
+pub fn check_inbound_auth(headers: &HeaderMap, mode: &AuthMode) -> Result<(), StatusCode> {
+    match mode {
+        AuthMode::Open => Ok(()),
+        AuthMode::StaticToken(expected) => match bearer_token(headers) {
+            Some(token) if token.as_bytes().ct_eq(expected.as_bytes()).into() => Ok(()),
+            _ => Err(StatusCode::UNAUTHORIZED),
+        },
+        AuthMode::Jwt(secret) => {
+            let token = bearer_token(headers).ok_or(StatusCode::UNAUTHORIZED)?;
+            validate_jwt(token, secret).map_err(|_| StatusCode::UNAUTHORIZED)
+        }
+    }
+}
+
+fn validate_jwt(token: &str, secret: &str) -> Result<(), Box<dyn std::error::Error>> {
+    use jsonwebtoken::{Algorithm, DecodingKey, Validation, decode};
+
+    let mut validation = Validation::new(Algorithm::HS256);
+    validation.set_required_spec_claims(&["exp", "iat"]);
+
+    decode::<Claims>(token, &DecodingKey::from_secret(secret.as_bytes()), &validation)?;
+
+    Ok(())
+}
+
+/// Attaches the configured upstream API key (if any) as a Bearer `Authorization`
+/// header on the outgoing request to LM Studio.
+pub fn apply_upstream_auth(
+    req: reqwest::RequestBuilder,
+    upstream_api_key: Option<&str>,
+) -> reqwest::RequestBuilder {
+    match upstream_api_key {
+        Some(key) => req.bearer_auth(key),
+        None => req,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+    use jsonwebtoken::{EncodingKey, Header, encode};
+
+    fn headers_with_bearer(token: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            axum::http::header::AUTHORIZATION,
+            HeaderValue::from_str(&format!("Bearer {}", token)).unwrap(),
+        );
+        headers
+    }
+
+    fn jwt(secret: &str, exp_offset_secs: i64) -> String {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+        let claims = Claims {
+            iat: now as u64,
+            exp: (now + exp_offset_secs) as u64,
+        };
+        encode(
+            &Header::new(jsonwebtoken::Algorithm::HS256),
+            &claims,
+            &EncodingKey::from_secret(secret.as_bytes()),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn bearer_token_extracts_token_from_authorization_header() {
+        let headers = headers_with_bearer("secret-token");
+        assert_eq!(bearer_token(&headers), Some("secret-token"));
+    }
+
+    #[test]
+    fn bearer_token_is_none_without_authorization_header() {
+        assert_eq!(bearer_token(&HeaderMap::new()), None);
+    }
+
+    #[test]
+    fn bearer_token_is_none_without_bearer_prefix() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            axum::http::header::AUTHORIZATION,
+            HeaderValue::from_static("Basic dXNlcjpwYXNz"),
+        );
+        assert_eq!(bearer_token(&headers), None);
+    }
+
+    #[test]
+    fn open_mode_allows_any_request() {
+        assert!(check_inbound_auth(&HeaderMap::new(), &AuthMode::Open).is_ok());
+    }
+
+    #[test]
+    fn static_token_mode_accepts_matching_token() {
+        let mode = AuthMode::StaticToken("correct-token".to_string());
+        let headers = headers_with_bearer("correct-token");
+        assert!(check_inbound_auth(&headers, &mode).is_ok());
+    }
+
+    #[test]
+    fn static_token_mode_rejects_mismatched_token() {
+        let mode = AuthMode::StaticToken("correct-token".to_string());
+        let headers = headers_with_bearer("wrong-token");
+        assert_eq!(
+            check_inbound_auth(&headers, &mode),
+            Err(StatusCode::UNAUTHORIZED)
+        );
+    }
+
+    #[test]
+    fn static_token_mode_rejects_missing_header() {
+        let mode = AuthMode::StaticToken("correct-token".to_string());
+        assert_eq!(
+            check_inbound_auth(&HeaderMap::new(), &mode),
+            Err(StatusCode::UNAUTHORIZED)
+        );
+    }
+
+    #[test]
+    fn jwt_mode_accepts_validly_signed_unexpired_token() {
+        let mode = AuthMode::Jwt("hmac-secret".to_string());
+        let headers = headers_with_bearer(&jwt("hmac-secret", 3600));
+        assert!(check_inbound_auth(&headers, &mode).is_ok());
+    }
+
+    #[test]
+    fn jwt_mode_rejects_expired_token() {
+        let mode = AuthMode::Jwt("hmac-secret".to_string());
+        let headers = headers_with_bearer(&jwt("hmac-secret", -3600));
+        assert_eq!(
+            check_inbound_auth(&headers, &mode),
+            Err(StatusCode::UNAUTHORIZED)
+        );
+    }
+
+    #[test]
+    fn jwt_mode_rejects_token_signed_with_wrong_secret() {
+        let mode = AuthMode::Jwt("hmac-secret".to_string());
+        let headers = headers_with_bearer(&jwt("other-secret", 3600));
+        assert_eq!(
+            check_inbound_auth(&headers, &mode),
+            Err(StatusCode::UNAUTHORIZED)
+        );
+    }
+}
@@ -0,0 +1,231 @@
+//! In-memory conditional-GET cache for the models-listing endpoints.
+//!
+//! LM Studio's model list changes rarely, but Copilot and `generate_config` poll it
+//! repeatedly. We cache the upstream body plus a strong ETag/Last-Modified pair for a
+//! configurable TTL, and honor `If-None-Match`/`If-Modified-Since` from the client
+//! with a `304 Not Modified`.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use axum::body::Body;
+use axum::http::{HeaderMap, StatusCode, header};
+use axum::response::Response;
+use bytes::Bytes;
+use sha2::{Digest, Sha256};
+
+use crate::auth;
+use crate::{CONFIG, HTTP_CLIENT, METRICS};
+
+const MODELS_PATHS: &[&str] = &["/v1/models", "/api/v0/models"];
+
+pub fn is_models_path(path: &str) -> bool {
+    MODELS_PATHS.contains(&path)
+}
+
+struct CachedModels {
+    body: Bytes,
+    etag: String,
+    last_modified: SystemTime,
+    fetched_at: Instant,
+}
+
+pub struct ModelsCache {
+    entries: Mutex<HashMap<String, CachedModels>>,
+}
+
+impl ModelsCache {
+    pub fn new() -> Self {
+        ModelsCache {
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Serves a GET to a models-listing path from cache, refreshing from upstream if the
+    /// TTL has expired, and honoring conditional-GET validators from the client.
+    pub async fn serve(
+        &self,
+        path: &str,
+        request_headers: &HeaderMap,
+        ttl: Duration,
+    ) -> Result<Response, StatusCode> {
+        let cached = {
+            let entries = self.entries.lock().unwrap();
+            entries.get(path).filter(|e| e.fetched_at.elapsed() < ttl).map(|e| {
+                (e.body.clone(), e.etag.clone(), e.last_modified)
+            })
+        };
+
+        let (body, etag, last_modified) = match cached {
+            Some(hit) => hit,
+            None => self.refresh(path).await?,
+        };
+
+        if client_has_fresh_copy(request_headers, &etag, last_modified) {
+            let mut response = Response::new(Body::empty());
+            *response.status_mut() = StatusCode::NOT_MODIFIED;
+            apply_validators(response.headers_mut(), &etag, last_modified);
+            return Ok(response);
+        }
+
+        let mut response = Response::new(Body::from(body));
+        apply_validators(response.headers_mut(), &etag, last_modified);
+        response
+            .headers_mut()
+            .insert(header::CONTENT_TYPE, "application/json".parse().unwrap());
+        Ok(response)
+    }
+
+    async fn refresh(&self, path: &str) -> Result<(Bytes, String, SystemTime), StatusCode> {
+        let config = CONFIG.get().expect("Config not initialized");
+        let client = HTTP_CLIENT.get().expect("HTTP client not initialized");
+
+        let url = format!("{}{}", config.lmstudio_url.trim_end_matches('/'), path);
+        let req = auth::apply_upstream_auth(client.get(&url), config.upstream_api_key.as_deref());
+        let request_started_at = Instant::now();
+        let result = req.send().await;
+
+        if let Some(metrics) = METRICS.get() {
+            let status = result
+                .as_ref()
+                .map(|resp| resp.status().as_u16())
+                .unwrap_or(StatusCode::BAD_GATEWAY.as_u16());
+            metrics.record_request("GET", path, status, request_started_at.elapsed());
+        }
+
+        let resp = result.map_err(|_| StatusCode::BAD_GATEWAY)?;
+        if !resp.status().is_success() {
+            return Err(StatusCode::BAD_GATEWAY);
+        }
+        let body = resp.bytes().await.map_err(|_| StatusCode::BAD_GATEWAY)?;
+
+        let etag = format!("\"{:x}\"", Sha256::digest(&body));
+        let last_modified = SystemTime::now();
+
+        let mut entries = self.entries.lock().unwrap();
+        entries.insert(
+            path.to_string(),
+            CachedModels {
+                body: body.clone(),
+                etag: etag.clone(),
+                last_modified,
+                fetched_at: Instant::now(),
+            },
+        );
+
+        Ok((body, etag, last_modified))
+    }
+}
+
+fn apply_validators(headers: &mut HeaderMap, etag: &str, last_modified: SystemTime) {
+    headers.insert(header::ETAG, etag.parse().unwrap());
+    headers.insert(
+        header::LAST_MODIFIED,
+        httpdate::fmt_http_date(last_modified).parse().unwrap(),
+    );
+}
+
+fn client_has_fresh_copy(headers: &HeaderMap, etag: &str, last_modified: SystemTime) -> bool {
+    if let Some(if_none_match) = headers.get(header::IF_NONE_MATCH).and_then(|v| v.to_str().ok()) {
+        return if_none_match
+            .split(',')
+            .map(str::trim)
+            .any(|candidate| candidate == etag || candidate == "*");
+    }
+
+    if let Some(if_modified_since) = headers
+        .get(header::IF_MODIFIED_SINCE)
+        .and_then(|v| v.to_str().ok())
+        && let Ok(since) = httpdate::parse_http_date(if_modified_since)
+    {
+        // HTTP validators only carry second-granularity timestamps.
+        let since_secs = since.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        let last_modified_secs = last_modified
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        return last_modified_secs <= since_secs;
+    }
+
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers_with(name: header::HeaderName, value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(name, value.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn is_models_path_matches_known_paths() {
+        assert!(is_models_path("/v1/models"));
+        assert!(is_models_path("/api/v0/models"));
+        assert!(!is_models_path("/v1/chat/completions"));
+    }
+
+    #[test]
+    fn if_none_match_hit_on_matching_etag() {
+        let headers = headers_with(header::IF_NONE_MATCH, "\"abc123\"");
+        assert!(client_has_fresh_copy(&headers, "\"abc123\"", SystemTime::now()));
+    }
+
+    #[test]
+    fn if_none_match_hit_on_wildcard() {
+        let headers = headers_with(header::IF_NONE_MATCH, "*");
+        assert!(client_has_fresh_copy(&headers, "\"abc123\"", SystemTime::now()));
+    }
+
+    #[test]
+    fn if_none_match_miss_on_different_etag() {
+        let headers = headers_with(header::IF_NONE_MATCH, "\"other-etag\"");
+        assert!(!client_has_fresh_copy(&headers, "\"abc123\"", SystemTime::now()));
+    }
+
+    #[test]
+    fn if_none_match_takes_precedence_over_if_modified_since() {
+        let mut headers = headers_with(header::IF_NONE_MATCH, "\"wrong\"");
+        headers.insert(
+            header::IF_MODIFIED_SINCE,
+            httpdate::fmt_http_date(SystemTime::now()).parse().unwrap(),
+        );
+        assert!(!client_has_fresh_copy(&headers, "\"abc123\"", SystemTime::now()));
+    }
+
+    #[test]
+    fn if_modified_since_hit_when_not_modified_since() {
+        let last_modified = SystemTime::now() - Duration::from_secs(60);
+        let headers = headers_with(
+            header::IF_MODIFIED_SINCE,
+            &httpdate::fmt_http_date(SystemTime::now()),
+        );
+        assert!(client_has_fresh_copy(&headers, "\"abc123\"", last_modified));
+    }
+
+    #[test]
+    fn if_modified_since_miss_when_modified_after() {
+        let last_modified = SystemTime::now();
+        let headers = headers_with(
+            header::IF_MODIFIED_SINCE,
+            &httpdate::fmt_http_date(SystemTime::now() - Duration::from_secs(60)),
+        );
+        assert!(!client_has_fresh_copy(&headers, "\"abc123\"", last_modified));
+    }
+
+    #[test]
+    fn no_validators_is_never_fresh() {
+        assert!(!client_has_fresh_copy(&HeaderMap::new(), "\"abc123\"", SystemTime::now()));
+    }
+
+    #[test]
+    fn apply_validators_sets_etag_and_last_modified_headers() {
+        let mut headers = HeaderMap::new();
+        apply_validators(&mut headers, "\"abc123\"", SystemTime::now());
+        assert_eq!(headers.get(header::ETAG).unwrap(), "\"abc123\"");
+        assert!(headers.get(header::LAST_MODIFIED).is_some());
+    }
+}
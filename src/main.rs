@@ -16,8 +16,17 @@ use tracing::{error, info, warn};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 use chrono::Datelike;
 
+mod auth;
+mod cache;
+mod metrics;
+mod reasoning;
+mod ssh_tunnel;
+
 static CONFIG: OnceLock<ServeConfig> = OnceLock::new();
 static HTTP_CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+static AUTH_MODE: OnceLock<auth::AuthMode> = OnceLock::new();
+static METRICS: OnceLock<metrics::Metrics> = OnceLock::new();
+static MODELS_CACHE: OnceLock<cache::ModelsCache> = OnceLock::new();
 
 #[derive(Parser, Debug)]
 #[command(name = "copilot-lmstudio-config")]
@@ -25,6 +34,18 @@ static HTTP_CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
 struct Cli {
     #[command(subcommand)]
     command: Option<Command>,
+
+    /// Output format: human-readable text, or a single structured JSON object (for
+    /// scripts and editor extensions)
+    #[arg(long, value_enum, global = true, default_value = "text")]
+    format: OutputFormat,
+}
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum OutputFormat {
+    #[default]
+    Text,
+    Json,
 }
 
 #[derive(clap::Subcommand, Debug)]
@@ -52,6 +73,52 @@ struct ServeConfig {
     /// Enable CORS (Cross-Origin Resource Sharing)
     #[arg(short, long, default_value_t = false)]
     cors: bool,
+
+    /// Require inbound requests to present this bearer token, rejecting mismatches with 401
+    #[arg(long, env = "PROXY_API_KEY", conflicts_with = "jwt_secret")]
+    require_api_key: Option<String>,
+
+    /// Require inbound requests to present a JWT signed with this HMAC secret (with exp/iat
+    /// claims) instead of a static token
+    #[arg(long, env = "PROXY_JWT_SECRET")]
+    jwt_secret: Option<String>,
+
+    /// Bearer token injected into the Authorization header of upstream (LM Studio) requests
+    #[arg(long, env = "PROXY_UPSTREAM_API_KEY")]
+    upstream_api_key: Option<String>,
+
+    /// SSH host (user@host) to tunnel through to reach a remote LM Studio instance
+    #[arg(long)]
+    ssh_host: Option<String>,
+
+    /// Port the SSH server listens on
+    #[arg(long, default_value_t = 22)]
+    ssh_port: u16,
+
+    /// Path to an SSH private key to authenticate with (falls back to ssh-agent/default keys)
+    #[arg(long)]
+    ssh_key: Option<String>,
+
+    /// Password to authenticate the SSH tunnel with (prefer --ssh-key where possible)
+    #[arg(long, env = "PROXY_SSH_PASSWORD")]
+    ssh_password: Option<String>,
+
+    /// Port LM Studio listens on on the remote host behind the SSH tunnel
+    #[arg(long, default_value_t = 1234)]
+    remote_lmstudio_port: u16,
+
+    /// Expose Prometheus metrics (request counts, upstream latency, token usage) on /metrics
+    #[arg(long, default_value_t = false)]
+    metrics: bool,
+
+    /// How long to cache /v1/models and /api/v0/models responses, in seconds (0 disables caching)
+    #[arg(long, default_value_t = 30)]
+    models_cache_ttl: u64,
+
+    /// Move reasoning/"thinking" text (from <think> tags or a reasoning field) into
+    /// reasoning_content on /v1/chat/completions responses, so Copilot renders it correctly
+    #[arg(long, default_value_t = false)]
+    normalize_reasoning: bool,
 }
 
 #[derive(clap::ValueEnum, Debug, Clone)]
@@ -77,27 +144,62 @@ struct GenerateConfigArgs {
     /// Path to VS Code settings.json file (prints to stdout if not provided)
     #[arg(long, conflicts_with = "settings")]
     settings_path: Option<String>,
+
+    /// Mark generated models as requiring an API key (set this when the proxy is started
+    /// with --require-api-key or --jwt-secret)
+    #[arg(long, default_value_t = false)]
+    requires_api_key: bool,
+
+    /// Apply changes to settings.json without prompting for confirmation (needed when
+    /// driving this command from a script or editor extension, since there's no
+    /// terminal to confirm against)
+    #[arg(long, default_value_t = false)]
+    yes: bool,
+
+    /// Output format, set from the global --format flag
+    #[arg(skip)]
+    format: OutputFormat,
 }
 
 #[tokio::main]
 async fn main() {
     let cli = Cli::parse();
 
+    let format = cli.format;
+
     match cli.command {
-        Some(Command::Serve(config)) => serve(config).await,
-        Some(Command::GenerateConfig(args)) => {
+        Some(Command::Serve(config)) => serve(config, format).await,
+        Some(Command::GenerateConfig(mut args)) => {
+            args.format = format;
             if let Err(e) = generate_config(args).await {
-                eprintln!("Error: {}", e);
+                if format == OutputFormat::Json {
+                    println!(
+                        "{}",
+                        json!({ "status": "error", "message": e.to_string() })
+                    );
+                } else {
+                    eprintln!("Error: {}", e);
+                }
                 std::process::exit(1);
             }
         }
         None => {
             // Default to serve if no subcommand provided
-            serve(ServeConfig::parse()).await
+            serve(ServeConfig::parse(), format).await
         }
     }
 }
 
+/// Prints a fatal startup error in the selected format and exits with status 1.
+fn fail_startup(format: OutputFormat, message: impl std::fmt::Display) -> ! {
+    if format == OutputFormat::Json {
+        println!("{}", json!({ "status": "error", "message": message.to_string() }));
+    } else {
+        eprintln!("Error: {}", message);
+    }
+    std::process::exit(1);
+}
+
 fn get_vscode_settings_path(
     editor: &VsCodeEditor,
 ) -> Result<std::path::PathBuf, Box<dyn std::error::Error>> {
@@ -237,11 +339,34 @@ struct CopilotConfig {
 
 type ModelsMap = std::collections::BTreeMap<String, CopilotConfig>;
 
+/// Summary of what changed in the settings file, reported in the `--format json`
+/// output.
+#[derive(Serialize, Debug)]
+struct DiffSummary {
+    file: String,
+    added: usize,
+    removed: usize,
+    changed: bool,
+}
+
+#[derive(Serialize, Debug)]
+struct GenerateConfigOutput {
+    status: &'static str,
+    models: Vec<String>,
+    #[serde(rename = "customOAIModels")]
+    custom_oai_models: ModelsMap,
+    diff: Option<DiffSummary>,
+}
+
 async fn generate_config(args: GenerateConfigArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let format = args.format;
+
     // Determine the settings path
     let final_settings_path = if let Some(ref editor) = args.settings {
         let path = get_vscode_settings_path(editor)?;
-        println!("Using settings file: {}", path.display());
+        if format == OutputFormat::Text {
+            println!("Using settings file: {}", path.display());
+        }
         Some(path.to_string_lossy().to_string())
     } else {
         args.settings_path
@@ -266,14 +391,14 @@ async fn generate_config(args: GenerateConfigArgs) -> Result<(), Box<dyn std::er
         Ok(resp) => resp,
         Err(e) => {
             if e.is_connect() {
-                eprintln!("\nError: Could not connect to LM Studio at {}", lmstudio_url);
-                eprintln!("\nPlease ensure:");
-                eprintln!("  1. LM Studio is running");
-                eprintln!("  2. Local server is started in LM Studio");
-                eprintln!("  3. Server is listening on the correct port");
-                eprintln!("\nIf LM Studio is running on a different port, use:");
-                eprintln!("  --lmstudio-url http://localhost:PORT");
-                std::process::exit(1);
+                return Err(format!(
+                    "Could not connect to LM Studio at {}. Please ensure: \
+                     (1) LM Studio is running, (2) its local server is started, \
+                     (3) it is listening on the correct port. If LM Studio is on a \
+                     different port, pass --lmstudio-url http://localhost:PORT",
+                    lmstudio_url
+                )
+                .into());
             } else {
                 return Err(format!("Error sending request to {}: {}", models_url, e).into());
             }
@@ -317,20 +442,40 @@ async fn generate_config(args: GenerateConfigArgs) -> Result<(), Box<dyn std::er
             thinking: true,
             max_input_tokens: max_context,
             max_output_tokens: max_context,
-            requires_api_key: false,
+            requires_api_key: args.requires_api_key,
         };
 
         config_map.insert(model.id, copilot_config);
     }
 
+    let models: Vec<String> = config_map.keys().cloned().collect();
+
     // Output configuration
-    if let Some(settings_path) = final_settings_path {
-        update_settings_file(&settings_path, &config_map)?;
+    let diff = if let Some(settings_path) = final_settings_path {
+        Some(update_settings_file(
+            &settings_path,
+            &config_map,
+            format,
+            format == OutputFormat::Json || args.yes,
+        )?)
     } else {
-        let output = json!({
-            "github.copilot.chat.customOAIModels": config_map
-        });
-        println!("{}", serde_json::to_string_pretty(&output)?);
+        if format == OutputFormat::Text {
+            let output = json!({
+                "github.copilot.chat.customOAIModels": &config_map
+            });
+            println!("{}", serde_json::to_string_pretty(&output)?);
+        }
+        None
+    };
+
+    if format == OutputFormat::Json {
+        let output = GenerateConfigOutput {
+            status: "ok",
+            models,
+            custom_oai_models: config_map,
+            diff,
+        };
+        println!("{}", serde_json::to_string(&output)?);
     }
 
     Ok(())
@@ -579,10 +724,27 @@ fn try_update_custom_oai_models_in_text(
     None
 }
 
+fn count_model_key_changes(old_content: &str, config: &ModelsMap) -> (usize, usize) {
+    let old_keys: std::collections::BTreeSet<String> = json5::from_str::<Value>(old_content)
+        .ok()
+        .and_then(|v| v.get("github.copilot.chat.customOAIModels").cloned())
+        .and_then(|v| v.as_object().cloned())
+        .map(|obj| obj.keys().cloned().collect())
+        .unwrap_or_default();
+
+    let new_keys: std::collections::BTreeSet<String> = config.keys().cloned().collect();
+
+    let added = new_keys.difference(&old_keys).count();
+    let removed = old_keys.difference(&new_keys).count();
+    (added, removed)
+}
+
 fn update_settings_file(
     settings_path: &str,
     config: &ModelsMap,
-) -> Result<(), Box<dyn std::error::Error>> {
+    format: OutputFormat,
+    auto_apply: bool,
+) -> Result<DiffSummary, Box<dyn std::error::Error>> {
     use std::fs;
     use std::path::PathBuf;
 
@@ -605,10 +767,12 @@ fn update_settings_file(
             updated
         } else {
             let mut settings: Value = json5::from_str(&old_content).unwrap_or_else(|e| {
-                eprintln!(
-                    "Warning: Could not parse existing settings ({}), creating new structure...",
-                    e
-                );
+                if format == OutputFormat::Text {
+                    eprintln!(
+                        "Warning: Could not parse existing settings ({}), creating new structure...",
+                        e
+                    );
+                }
                 json!({})
             });
             settings["github.copilot.chat.customOAIModels"] = serde_json::to_value(config)?;
@@ -620,11 +784,31 @@ fn update_settings_file(
         serialize_with_indent(&settings, &indent_unit)?
     };
 
-    // Show diff and ask for confirmation (if there are changes)
-    match show_diff_and_confirm(&old_content, &new_content, settings_path)? {
+    let (added, removed) = count_model_key_changes(&old_content, config);
+    let diff_summary = DiffSummary {
+        file: settings_path.to_string(),
+        added,
+        removed,
+        changed: old_content != new_content,
+    };
+
+    // --yes or --format json applies unattended (needed when there's no terminal to
+    // confirm against, e.g. driving this from a script, CI, or editor extension);
+    // otherwise show the diff and ask for confirmation (if there are changes).
+    let decision = if auto_apply {
+        if diff_summary.changed {
+            DiffDecision::Apply
+        } else {
+            DiffDecision::Unchanged
+        }
+    } else {
+        show_diff_and_confirm(&old_content, &new_content, settings_path)?
+    };
+
+    match decision {
         DiffDecision::Unchanged => {
             // Nothing to do, leave file and backup untouched.
-            return Ok(());
+            return Ok(diff_summary);
         }
         DiffDecision::Cancel => {
             println!("Operation cancelled by user");
@@ -659,32 +843,27 @@ fn update_settings_file(
         };
 
         fs::copy(&settings_file, &backup_path)?;
-        println!("Created backup at {}", backup_path.display());
+        if format == OutputFormat::Text {
+            println!("Created backup at {}", backup_path.display());
+        }
     }
 
     // Write back to file (as regular JSON with proper formatting)
     fs::write(&settings_file, new_content)?;
 
-    println!(
-        "Updated {} with {} models",
-        settings_file.display(),
-        config.len()
-    );
+    if format == OutputFormat::Text {
+        println!(
+            "Updated {} with {} models",
+            settings_file.display(),
+            config.len()
+        );
+    }
 
-    Ok(())
+    Ok(diff_summary)
 }
 
-async fn serve(config: ServeConfig) {
-    CONFIG.set(config.clone()).expect("Failed to set config");
-
-    // Initialize HTTP client (reused for all requests for connection pooling)
-    let client = reqwest::Client::builder()
-        .http1_only() // LMStudio might not support HTTP/2
-        .build()
-        .expect("Failed to create HTTP client");
-    HTTP_CLIENT.set(client).expect("Failed to set HTTP client");
-
-    // Initialize tracing
+async fn serve(mut config: ServeConfig, format: OutputFormat) {
+    // Initialize tracing first so tunnel setup below can log through it.
     tracing_subscriber::registry()
         .with(
             tracing_subscriber::EnvFilter::try_from_default_env()
@@ -693,6 +872,41 @@ async fn serve(config: ServeConfig) {
         .with(tracing_subscriber::fmt::layer())
         .init();
 
+    AUTH_MODE
+        .set(auth::AuthMode::from_config(&config))
+        .unwrap_or_else(|_| panic!("Failed to set auth mode"));
+
+    // If a remote LM Studio host is configured, open the SSH tunnel first and
+    // rewrite lmstudio_url to point at the local forwarded port, so proxy_handler
+    // and generate_config keep working unchanged.
+    let ssh_tunnel = match ssh_tunnel::spawn(&config).await {
+        Ok(Some(tunnel)) => {
+            config.lmstudio_url = format!("http://127.0.0.1:{}", tunnel.local_port);
+            Some(tunnel)
+        }
+        Ok(None) => None,
+        Err(e) => fail_startup(format, format_args!("failed to establish SSH tunnel to LM Studio: {}", e)),
+    };
+
+    CONFIG.set(config.clone()).expect("Failed to set config");
+
+    if config.metrics {
+        METRICS
+            .set(metrics::init())
+            .unwrap_or_else(|_| panic!("Failed to set metrics registry"));
+    }
+
+    MODELS_CACHE
+        .set(cache::ModelsCache::new())
+        .unwrap_or_else(|_| panic!("Failed to set models cache"));
+
+    // Initialize HTTP client (reused for all requests for connection pooling)
+    let client = match reqwest::Client::builder().http1_only().build() {
+        Ok(client) => client,
+        Err(e) => fail_startup(format, format_args!("failed to create HTTP client: {}", e)),
+    };
+    HTTP_CLIENT.set(client).expect("Failed to set HTTP client");
+
     let bind_addr = if config.bind_all {
         format!("0.0.0.0:{}", config.port)
     } else {
@@ -701,10 +915,31 @@ async fn serve(config: ServeConfig) {
 
     info!("Copilot-LMStudio Proxy starting");
     info!("  Listening: http://{}", bind_addr);
-    info!("  Upstream: {}", config.lmstudio_url);
+    if let Some(ssh_host) = &config.ssh_host {
+        info!(
+            "  Upstream: {} (tunneled via {})",
+            config.lmstudio_url, ssh_host
+        );
+    } else {
+        info!("  Upstream: {}", config.lmstudio_url);
+    }
     if config.cors {
         info!("  CORS: enabled");
     }
+    match AUTH_MODE.get().expect("Auth mode not initialized") {
+        auth::AuthMode::Open => {}
+        auth::AuthMode::StaticToken(_) => info!("  Auth: static bearer token required"),
+        auth::AuthMode::Jwt(_) => info!("  Auth: signed JWT required"),
+    }
+    if config.metrics {
+        info!("  Metrics: enabled on /metrics");
+    }
+    if config.models_cache_ttl > 0 {
+        info!("  Models cache TTL: {}s", config.models_cache_ttl);
+    }
+    if config.normalize_reasoning {
+        info!("  Reasoning normalization: enabled");
+    }
 
     let mut app = Router::new().fallback(any(proxy_handler));
 
@@ -718,10 +953,26 @@ async fn serve(config: ServeConfig) {
         app = app.layer(cors);
     }
 
-    let listener = tokio::net::TcpListener::bind(&bind_addr).await.unwrap();
+    let listener = match tokio::net::TcpListener::bind(&bind_addr).await {
+        Ok(listener) => listener,
+        Err(e) => fail_startup(format, format_args!("failed to bind {}: {}", bind_addr, e)),
+    };
 
     info!("Proxy ready!");
-    axum::serve(listener, app).await.unwrap();
+    tokio::select! {
+        result = axum::serve(listener, app) => {
+            if let Err(e) = result {
+                fail_startup(format, format_args!("server error: {}", e));
+            }
+        }
+        _ = tokio::signal::ctrl_c() => {
+            info!("Shutting down...");
+        }
+    }
+
+    if let Some(tunnel) = ssh_tunnel {
+        tunnel.shutdown().await;
+    }
 }
 
 async fn proxy_handler(req: Request) -> Result<Response, StatusCode> {
@@ -742,6 +993,29 @@ async fn proxy_handler(req: Request) -> Result<Response, StatusCode> {
         }
     );
 
+    let auth_mode = AUTH_MODE.get().expect("Auth mode not initialized");
+    if let Err(status) = auth::check_inbound_auth(&parts.headers, auth_mode) {
+        warn!("Rejected unauthenticated request to {} {}", method, path);
+        return Err(status);
+    }
+
+    if path == "/metrics" && CONFIG.get().expect("Config not initialized").metrics {
+        let body = METRICS.get().expect("Metrics not initialized").render();
+        let mut response = Response::new(Body::from(body));
+        response
+            .headers_mut()
+            .insert("content-type", "text/plain; version=0.0.4".parse().unwrap());
+        return Ok(response);
+    }
+
+    let cache_ttl = CONFIG.get().expect("Config not initialized").models_cache_ttl;
+    if method == axum::http::Method::GET && cache_ttl > 0 && cache::is_models_path(path) {
+        let models_cache = MODELS_CACHE.get().expect("Models cache not initialized");
+        return models_cache
+            .serve(path, &parts.headers, std::time::Duration::from_secs(cache_ttl))
+            .await;
+    }
+
     // Read the original body
     let body_bytes = match body.collect().await {
         Ok(collected) => collected.to_bytes(),
@@ -784,11 +1058,15 @@ async fn proxy_handler(req: Request) -> Result<Response, StatusCode> {
         let name_str = name.as_str();
         // Skip host and headers that might cause issues. Reqwest recalculates
         // connection management, compression, and body length on our behalf.
+        // Authorization is also skipped: the client's header was already validated
+        // against our own auth mode and must not be forwarded verbatim, since
+        // apply_upstream_auth() below appends its own Authorization header.
         if name_str == "host"
             || name_str.starts_with("sec-")
             || name_str == "connection"
             || name_str == "accept-encoding"
             || name_str == "content-length"
+            || name_str == "authorization"
         {
             continue;
         }
@@ -796,10 +1074,15 @@ async fn proxy_handler(req: Request) -> Result<Response, StatusCode> {
         upstream_req = upstream_req.header(name, value);
     }
 
+    // Inject the upstream API key, if configured, overriding whatever Authorization
+    // header the client sent (that one was already validated against our own auth mode).
+    upstream_req = auth::apply_upstream_auth(upstream_req, config.upstream_api_key.as_deref());
+
     // Add body
     upstream_req = upstream_req.body(fixed_body_bytes);
 
     // Send request to LMStudio
+    let request_started_at = std::time::Instant::now();
     let upstream_response = match upstream_req.send().await {
         Ok(resp) => resp,
         Err(e) => {
@@ -815,6 +1098,10 @@ async fn proxy_handler(req: Request) -> Result<Response, StatusCode> {
         warn!("Response: {}", status);
     }
 
+    if let Some(metrics) = METRICS.get() {
+        metrics.record_request(method.as_str(), path, status.as_u16(), request_started_at.elapsed());
+    }
+
     // Check if this is a streaming response
     let is_streaming = headers
         .get("content-type")
@@ -827,16 +1114,50 @@ async fn proxy_handler(req: Request) -> Result<Response, StatusCode> {
 
     if is_streaming {
         // Handle streaming response
+        let should_normalize_reasoning = config.normalize_reasoning && path == "/v1/chat/completions";
+        let reasoning_state = std::sync::Arc::new(std::sync::Mutex::new(reasoning::StreamNormalizer::new()));
+        let flush_state = reasoning_state.clone();
+
         let stream = upstream_response.bytes_stream();
-        let fixed_stream = stream.map(move |chunk_result| match chunk_result {
-            Ok(chunk) => match fix_streaming_chunk(&chunk) {
-                Ok(fixed) => Ok(fixed),
-                Err(_) => Ok(chunk),
-            },
-            Err(e) => Err(std::io::Error::other(e)),
+        let fixed_stream = stream.flat_map(move |chunk_result| {
+            let chunks: Vec<Result<Bytes, std::io::Error>> = match chunk_result {
+                Ok(chunk) => {
+                    if let Some(metrics) = METRICS.get() {
+                        metrics.record_streaming_chunk_usage(&chunk);
+                    }
+                    let fixed = match fix_streaming_chunk(&chunk) {
+                        Ok(fixed) => fixed,
+                        Err(_) => chunk,
+                    };
+                    if !should_normalize_reasoning {
+                        vec![Ok(fixed)]
+                    } else if reasoning::is_done_chunk(&fixed) {
+                        let mut out = Vec::new();
+                        if let Some(flush) = reasoning_state.lock().unwrap().flush() {
+                            out.push(Ok(flush));
+                        }
+                        out.push(Ok(fixed));
+                        out
+                    } else {
+                        vec![Ok(reasoning_state.lock().unwrap().process_sse_chunk(&fixed))]
+                    }
+                }
+                Err(e) => vec![Err(std::io::Error::other(e))],
+            };
+            futures::stream::iter(chunks)
         });
 
-        let body = Body::from_stream(fixed_stream);
+        // Covers streams that end without ever sending `[DONE]`; a no-op if the
+        // `[DONE]` branch above already drained any pending reasoning text.
+        let flush_tail = futures::stream::once(async move {
+            if !should_normalize_reasoning {
+                return None;
+            }
+            flush_state.lock().unwrap().flush().map(Ok)
+        })
+        .filter_map(futures::future::ready);
+
+        let body = Body::from_stream(fixed_stream.chain(flush_tail));
         let mut response = Response::new(body);
         *response.status_mut() = status;
         *response.headers_mut() = headers;
@@ -861,6 +1182,22 @@ async fn proxy_handler(req: Request) -> Result<Response, StatusCode> {
             body_bytes
         };
 
+        if let Some(metrics) = METRICS.get() {
+            metrics.record_response_usage(&fixed_body_bytes);
+        }
+
+        let fixed_body_bytes = if config.normalize_reasoning
+            && path == "/v1/chat/completions"
+            && is_json_response(&headers)
+        {
+            match reasoning::normalize_response_body(&fixed_body_bytes) {
+                Ok(normalized) => normalized,
+                Err(_) => fixed_body_bytes,
+            }
+        } else {
+            fixed_body_bytes
+        };
+
         let mut response = Response::new(Body::from(fixed_body_bytes));
         *response.status_mut() = status;
         *response.headers_mut() = headers;
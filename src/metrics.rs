@@ -0,0 +1,211 @@
+//! Prometheus metrics for the proxy: request counts, upstream latency, and token usage.
+//!
+//! Kept in a process-wide registry alongside the existing `CONFIG`/`HTTP_CLIENT`
+//! `OnceLock`s in `main.rs`. Only populated when `--metrics` is passed.
+
+use std::time::Duration;
+
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, Opts, Registry, TextEncoder,
+};
+use serde_json::Value;
+
+pub struct Metrics {
+    registry: Registry,
+    requests_total: IntCounterVec,
+    upstream_latency_seconds: HistogramVec,
+    prompt_tokens_total: IntCounter,
+    completion_tokens_total: IntCounter,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new();
+
+        let requests_total = IntCounterVec::new(
+            Opts::new(
+                "proxy_requests_total",
+                "Total requests proxied to the upstream LM Studio server",
+            ),
+            &["method", "path", "upstream_status"],
+        )
+        .expect("failed to build proxy_requests_total");
+
+        let upstream_latency_seconds = HistogramVec::new(
+            HistogramOpts::new(
+                "proxy_upstream_latency_seconds",
+                "Upstream round-trip latency in seconds",
+            ),
+            &["method", "path"],
+        )
+        .expect("failed to build proxy_upstream_latency_seconds");
+
+        let prompt_tokens_total = IntCounter::new(
+            "proxy_prompt_tokens_total",
+            "Total prompt tokens reported by upstream chat completions",
+        )
+        .expect("failed to build proxy_prompt_tokens_total");
+
+        let completion_tokens_total = IntCounter::new(
+            "proxy_completion_tokens_total",
+            "Total completion tokens reported by upstream chat completions",
+        )
+        .expect("failed to build proxy_completion_tokens_total");
+
+        registry
+            .register(Box::new(requests_total.clone()))
+            .expect("failed to register requests_total collector");
+        registry
+            .register(Box::new(upstream_latency_seconds.clone()))
+            .expect("failed to register upstream_latency_seconds collector");
+        registry
+            .register(Box::new(prompt_tokens_total.clone()))
+            .expect("failed to register prompt_tokens_total collector");
+        registry
+            .register(Box::new(completion_tokens_total.clone()))
+            .expect("failed to register completion_tokens_total collector");
+
+        Metrics {
+            registry,
+            requests_total,
+            upstream_latency_seconds,
+            prompt_tokens_total,
+            completion_tokens_total,
+        }
+    }
+
+    pub fn record_request(&self, method: &str, path: &str, upstream_status: u16, latency: Duration) {
+        self.requests_total
+            .with_label_values(&[method, path, &upstream_status.to_string()])
+            .inc();
+        self.upstream_latency_seconds
+            .with_label_values(&[method, path])
+            .observe(latency.as_secs_f64());
+    }
+
+    fn record_usage_value(&self, usage: &Value) {
+        if let Some(prompt) = usage.get("prompt_tokens").and_then(Value::as_u64) {
+            self.prompt_tokens_total.inc_by(prompt);
+        }
+        if let Some(completion) = usage.get("completion_tokens").and_then(Value::as_u64) {
+            self.completion_tokens_total.inc_by(completion);
+        }
+    }
+
+    /// Scrapes a buffered, non-streaming chat-completion response body for its `usage` object.
+    pub fn record_response_usage(&self, body: &[u8]) {
+        if let Ok(json) = serde_json::from_slice::<Value>(body)
+            && let Some(usage) = json.get("usage")
+        {
+            self.record_usage_value(usage);
+        }
+    }
+
+    /// Scrapes a single SSE `data:` chunk for a final `usage` object, without buffering
+    /// the rest of the stream. LM Studio emits `usage` on the terminal chunk.
+    pub fn record_streaming_chunk_usage(&self, chunk: &[u8]) {
+        let Ok(chunk_str) = std::str::from_utf8(chunk) else {
+            return;
+        };
+        for line in chunk_str.lines() {
+            let Some(data) = line.strip_prefix("data: ") else {
+                continue;
+            };
+            let data = data.trim();
+            if data.is_empty() || data == "[DONE]" {
+                continue;
+            }
+            if let Ok(json) = serde_json::from_str::<Value>(data)
+                && let Some(usage) = json.get("usage")
+            {
+                self.record_usage_value(usage);
+            }
+        }
+    }
+
+    pub fn render(&self) -> String {
+        let encoder = TextEncoder::new();
+        let metric_families = self.registry.gather();
+        let mut buf = Vec::new();
+        encoder
+            .encode(&metric_families, &mut buf)
+            .expect("failed to encode metrics");
+        String::from_utf8(buf).expect("metrics output was not valid UTF-8")
+    }
+}
+
+pub fn init() -> Metrics {
+    Metrics::new()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn record_request_populates_counter_and_histogram() {
+        let metrics = Metrics::new();
+        metrics.record_request("GET", "/v1/models", 200, Duration::from_millis(50));
+
+        let rendered = metrics.render();
+        assert!(rendered.contains(
+            "proxy_requests_total{method=\"GET\",path=\"/v1/models\",upstream_status=\"200\"} 1"
+        ));
+        assert!(rendered.contains("proxy_upstream_latency_seconds"));
+    }
+
+    #[test]
+    fn record_response_usage_parses_buffered_json_body() {
+        let metrics = Metrics::new();
+        let body = json!({ "usage": { "prompt_tokens": 10, "completion_tokens": 5 } }).to_string();
+        metrics.record_response_usage(body.as_bytes());
+
+        let rendered = metrics.render();
+        assert!(rendered.contains("proxy_prompt_tokens_total 10"));
+        assert!(rendered.contains("proxy_completion_tokens_total 5"));
+    }
+
+    #[test]
+    fn record_response_usage_ignores_bodies_without_usage() {
+        let metrics = Metrics::new();
+        metrics.record_response_usage(b"{\"choices\":[]}");
+
+        let rendered = metrics.render();
+        assert!(rendered.contains("proxy_prompt_tokens_total 0"));
+        assert!(rendered.contains("proxy_completion_tokens_total 0"));
+    }
+
+    #[test]
+    fn record_streaming_chunk_usage_parses_final_sse_usage_chunk() {
+        let metrics = Metrics::new();
+        let chunk = format!(
+            "data: {}\n\n",
+            json!({ "usage": { "prompt_tokens": 3, "completion_tokens": 7 } })
+        );
+        metrics.record_streaming_chunk_usage(chunk.as_bytes());
+
+        let rendered = metrics.render();
+        assert!(rendered.contains("proxy_prompt_tokens_total 3"));
+        assert!(rendered.contains("proxy_completion_tokens_total 7"));
+    }
+
+    #[test]
+    fn record_streaming_chunk_usage_ignores_done_sentinel() {
+        let metrics = Metrics::new();
+        metrics.record_streaming_chunk_usage(b"data: [DONE]\n\n");
+
+        let rendered = metrics.render();
+        assert!(rendered.contains("proxy_prompt_tokens_total 0"));
+    }
+
+    #[test]
+    fn two_independently_constructed_registries_do_not_panic() {
+        // Regression test: registering metrics via the macro-based global default
+        // registry would panic ("already registered") the second time `Metrics::new`
+        // ran in the same process. Building collectors directly and registering only
+        // into the local `Registry` avoids that footgun entirely.
+        let _first = Metrics::new();
+        let _second = Metrics::new();
+    }
+}
@@ -0,0 +1,400 @@
+//! Normalizes reasoning/"thinking" output on `/v1/chat/completions` responses so VS
+//! Code's Copilot Chat renders it as a reasoning block instead of regular content.
+//!
+//! Models emit reasoning two ways: inline `<think>...</think>` tags in `content`, or a
+//! separate `reasoning_content`/`reasoning` field. Both are normalized into
+//! `reasoning_content`, stripped out of the visible `content`. Opt-in via
+//! `--normalize-reasoning`; responses without any reasoning pass through untouched.
+
+use std::collections::HashMap;
+
+use bytes::Bytes;
+use serde_json::{Value, json};
+
+const THINK_OPEN: &str = "<think>";
+const THINK_CLOSE: &str = "</think>";
+
+/// Returns true if this SSE chunk is the terminating `[DONE]` sentinel.
+pub fn is_done_chunk(chunk: &Bytes) -> bool {
+    std::str::from_utf8(chunk)
+        .ok()
+        .and_then(|s| s.strip_prefix("data: "))
+        .map(|s| s.trim() == "[DONE]")
+        .unwrap_or(false)
+}
+
+/// Normalizes a buffered, non-streaming chat-completion response body.
+pub fn normalize_response_body(body: &Bytes) -> Result<Bytes, Box<dyn std::error::Error>> {
+    let mut json: Value = serde_json::from_slice(body)?;
+
+    let Some(choices) = json.get_mut("choices").and_then(Value::as_array_mut) else {
+        return Ok(body.clone());
+    };
+
+    for choice in choices.iter_mut() {
+        let Some(message) = choice.get_mut("message").and_then(Value::as_object_mut) else {
+            continue;
+        };
+        normalize_message_fields(message);
+    }
+
+    Ok(Bytes::from(serde_json::to_vec(&json)?))
+}
+
+fn normalize_message_fields(message: &mut serde_json::Map<String, Value>) {
+    promote_reasoning_alias(message);
+
+    let Some(content) = message.get("content").and_then(Value::as_str) else {
+        return;
+    };
+    let (visible, reasoning) = extract_think_blocks(content);
+    if reasoning.is_empty() {
+        return;
+    }
+
+    message.insert("content".to_string(), Value::String(visible));
+    let entry = message
+        .entry("reasoning_content".to_string())
+        .or_insert_with(|| Value::String(String::new()));
+    let merged = match entry.as_str() {
+        Some(existing) if !existing.is_empty() => format!("{}\n{}", existing, reasoning),
+        _ => reasoning,
+    };
+    *entry = Value::String(merged);
+}
+
+fn promote_reasoning_alias(message: &mut serde_json::Map<String, Value>) {
+    if !message.contains_key("reasoning_content")
+        && let Some(reasoning) = message.remove("reasoning")
+    {
+        message.insert("reasoning_content".to_string(), reasoning);
+    }
+}
+
+/// Pulls all `<think>...</think>` blocks out of `content`, returning `(visible, reasoning)`.
+/// `reasoning` is empty when no tags are present.
+fn extract_think_blocks(content: &str) -> (String, String) {
+    let mut visible = String::new();
+    let mut reasoning = String::new();
+    let mut rest = content;
+
+    loop {
+        let Some(start) = rest.find(THINK_OPEN) else {
+            visible.push_str(rest);
+            break;
+        };
+        visible.push_str(&rest[..start]);
+        let after_open = &rest[start + THINK_OPEN.len()..];
+
+        match after_open.find(THINK_CLOSE) {
+            Some(end) => {
+                reasoning.push_str(&after_open[..end]);
+                rest = &after_open[end + THINK_CLOSE.len()..];
+            }
+            None => {
+                // Unterminated tag; treat the remainder as reasoning rather than dropping it.
+                reasoning.push_str(after_open);
+                break;
+            }
+        }
+    }
+
+    (visible.trim().to_string(), reasoning.trim().to_string())
+}
+
+#[derive(Default)]
+struct ChoiceState {
+    in_think: bool,
+    pending: String,
+}
+
+impl ChoiceState {
+    /// Feeds more `content` text through the tag scanner, returning `(visible, reasoning)`
+    /// text to emit now. Text that might be a partial tag is held back in `pending` until
+    /// the next chunk resolves it.
+    fn process(&mut self, incoming: &str) -> (String, String) {
+        self.pending.push_str(incoming);
+        let mut visible = String::new();
+        let mut reasoning = String::new();
+
+        loop {
+            let tag = if self.in_think { THINK_CLOSE } else { THINK_OPEN };
+
+            if let Some(idx) = self.pending.find(tag) {
+                let out = self.pending[..idx].to_string();
+                self.pending.drain(..idx + tag.len());
+                if self.in_think {
+                    reasoning.push_str(&out);
+                } else {
+                    visible.push_str(&out);
+                }
+                self.in_think = !self.in_think;
+                continue;
+            }
+
+            let safe_len = longest_non_matching_prefix_len(&self.pending, tag);
+            let out = self.pending[..safe_len].to_string();
+            self.pending.drain(..safe_len);
+            if self.in_think {
+                reasoning.push_str(&out);
+            } else {
+                visible.push_str(&out);
+            }
+            break;
+        }
+
+        (visible, reasoning)
+    }
+}
+
+/// Length of the longest prefix of `buf` that cannot possibly be the start of `tag`
+/// (i.e. it's safe to emit now; the remainder might still grow into `tag`).
+fn longest_non_matching_prefix_len(buf: &str, tag: &str) -> usize {
+    let max_suffix = tag.len().saturating_sub(1).min(buf.len());
+    for len in (1..=max_suffix).rev() {
+        if buf.ends_with(&tag[..len]) {
+            return buf.len() - len;
+        }
+    }
+    buf.len()
+}
+
+/// Per-stream state for normalizing SSE chat-completion deltas as they pass through,
+/// without buffering the whole stream. One instance per proxied response.
+#[derive(Default)]
+pub struct StreamNormalizer {
+    per_choice: HashMap<i64, ChoiceState>,
+}
+
+impl StreamNormalizer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Flushes any text still held back in per-choice `pending` buffers (e.g. a
+    /// trailing `<` or `</think` that never resolved into a full tag before the
+    /// stream ended). Routed to `reasoning_content` if the choice was mid-`<think>`
+    /// block, otherwise emitted as visible content. Returns `None` if there's
+    /// nothing to flush. Call this when `[DONE]` is observed, or when the
+    /// underlying stream ends.
+    pub fn flush(&mut self) -> Option<Bytes> {
+        let mut choices = Vec::new();
+        for (index, state) in self.per_choice.iter_mut() {
+            if state.pending.is_empty() {
+                continue;
+            }
+            let content = std::mem::take(&mut state.pending);
+            let delta = if state.in_think {
+                json!({ "content": "", "reasoning_content": content })
+            } else {
+                json!({ "content": content })
+            };
+            choices.push(json!({
+                "index": index,
+                "delta": delta,
+                "finish_reason": Value::Null,
+            }));
+        }
+        if choices.is_empty() {
+            return None;
+        }
+
+        let payload = json!({ "object": "chat.completion.chunk", "choices": choices });
+        serde_json::to_string(&payload)
+            .ok()
+            .map(|s| Bytes::from(format!("data: {}\n\n", s)))
+    }
+
+    /// Rewrites a single SSE `data:` chunk in place, moving reasoning text out of
+    /// `delta.content` and into `delta.reasoning_content`. Tool-call chunks and the
+    /// `[DONE]` sentinel pass through untouched, as does anything we can't parse.
+    pub fn process_sse_chunk(&mut self, chunk: &Bytes) -> Bytes {
+        let Ok(chunk_str) = std::str::from_utf8(chunk) else {
+            return chunk.clone();
+        };
+        let Some(data_line) = chunk_str.strip_prefix("data: ") else {
+            return chunk.clone();
+        };
+        let data_line = data_line.trim();
+        if data_line.is_empty() || data_line == "[DONE]" {
+            return chunk.clone();
+        }
+
+        let Ok(mut json) = serde_json::from_str::<Value>(data_line) else {
+            return chunk.clone();
+        };
+        let Some(choices) = json.get_mut("choices").and_then(Value::as_array_mut) else {
+            return chunk.clone();
+        };
+
+        let mut changed = false;
+        for choice in choices.iter_mut() {
+            let index = choice.get("index").and_then(Value::as_i64).unwrap_or(0);
+            let Some(delta) = choice.get_mut("delta").and_then(Value::as_object_mut) else {
+                continue;
+            };
+
+            if delta.contains_key("tool_calls") {
+                continue;
+            }
+
+            if !delta.contains_key("reasoning_content")
+                && let Some(reasoning) = delta.remove("reasoning")
+            {
+                delta.insert("reasoning_content".to_string(), reasoning);
+                changed = true;
+            }
+
+            let Some(content) = delta.get("content").and_then(Value::as_str).map(str::to_string)
+            else {
+                continue;
+            };
+
+            let state = self.per_choice.entry(index).or_default();
+            let (visible, reasoning) = state.process(&content);
+            if visible == content && reasoning.is_empty() {
+                continue;
+            }
+
+            changed = true;
+            delta.insert("content".to_string(), Value::String(visible));
+            if !reasoning.is_empty() {
+                let entry = delta
+                    .entry("reasoning_content".to_string())
+                    .or_insert_with(|| Value::String(String::new()));
+                let merged = match entry.as_str() {
+                    Some(existing) => format!("{}{}", existing, reasoning),
+                    None => reasoning,
+                };
+                *entry = Value::String(merged);
+            }
+        }
+
+        if !changed {
+            return chunk.clone();
+        }
+
+        match serde_json::to_string(&json) {
+            Ok(s) => Bytes::from(format!("data: {}\n\n", s)),
+            Err(_) => chunk.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sse_chunk(delta: Value) -> Bytes {
+        let payload = json!({ "choices": [{ "index": 0, "delta": delta }] });
+        Bytes::from(format!("data: {}\n\n", payload))
+    }
+
+    fn delta_content(chunk: &Bytes) -> Option<String> {
+        let s = std::str::from_utf8(chunk).unwrap();
+        let data = s.strip_prefix("data: ")?.trim();
+        let json: Value = serde_json::from_str(data).ok()?;
+        json["choices"][0]["delta"]["content"].as_str().map(str::to_string)
+    }
+
+    #[test]
+    fn extracts_think_block_from_content() {
+        let (visible, reasoning) = extract_think_blocks("<think>pondering</think>answer");
+        assert_eq!(visible, "answer");
+        assert_eq!(reasoning, "pondering");
+    }
+
+    #[test]
+    fn leaves_content_without_think_tags_untouched() {
+        let (visible, reasoning) = extract_think_blocks("just an answer");
+        assert_eq!(visible, "just an answer");
+        assert!(reasoning.is_empty());
+    }
+
+    #[test]
+    fn normalizes_non_streaming_response_with_think_tags() {
+        let body = Bytes::from(
+            json!({
+                "choices": [{ "message": { "role": "assistant", "content": "<think>hmm</think>hi" } }]
+            })
+            .to_string(),
+        );
+
+        let normalized = normalize_response_body(&body).unwrap();
+        let json: Value = serde_json::from_slice(&normalized).unwrap();
+        assert_eq!(json["choices"][0]["message"]["content"], "hi");
+        assert_eq!(json["choices"][0]["message"]["reasoning_content"], "hmm");
+    }
+
+    #[test]
+    fn leaves_response_without_reasoning_untouched() {
+        let body = Bytes::from(json!({ "choices": [{ "message": { "content": "hi" } }] }).to_string());
+        let normalized = normalize_response_body(&body).unwrap();
+        assert_eq!(normalized, body);
+    }
+
+    #[test]
+    fn choice_state_holds_back_ambiguous_tag_prefix_across_chunks() {
+        let mut state = ChoiceState::default();
+        let (visible, reasoning) = state.process("answer<thi");
+        assert_eq!(visible, "answer");
+        assert!(reasoning.is_empty());
+        assert_eq!(state.pending, "<thi");
+
+        let (visible, reasoning) = state.process("nk>secret</think>done");
+        assert_eq!(visible, "done");
+        assert_eq!(reasoning, "secret");
+        assert!(state.pending.is_empty());
+    }
+
+    #[test]
+    fn stream_normalizer_splits_think_block_straddling_two_chunks() {
+        let mut normalizer = StreamNormalizer::new();
+        let first = normalizer.process_sse_chunk(&sse_chunk(json!({ "content": "<think>" })));
+        assert_eq!(delta_content(&first).as_deref(), Some(""));
+
+        let second = normalizer.process_sse_chunk(&sse_chunk(json!({ "content": "secret</think>hi" })));
+        let s = std::str::from_utf8(&second).unwrap();
+        assert!(s.contains("\"reasoning_content\":\"secret\""));
+        assert!(s.contains("\"content\":\"hi\""));
+    }
+
+    #[test]
+    fn stream_normalizer_leaves_tool_call_chunks_untouched() {
+        let mut normalizer = StreamNormalizer::new();
+        let chunk = sse_chunk(json!({ "tool_calls": [{ "id": "1" }] }));
+        let out = normalizer.process_sse_chunk(&chunk);
+        assert_eq!(out, chunk);
+    }
+
+    #[test]
+    fn stream_normalizer_passes_done_sentinel_through() {
+        let mut normalizer = StreamNormalizer::new();
+        let chunk = Bytes::from("data: [DONE]\n\n");
+        assert!(is_done_chunk(&chunk));
+        assert_eq!(normalizer.process_sse_chunk(&chunk), chunk);
+    }
+
+    #[test]
+    fn flush_emits_ambiguous_tail_left_pending_at_stream_end() {
+        let mut normalizer = StreamNormalizer::new();
+        normalizer.process_sse_chunk(&sse_chunk(json!({ "content": "answer<th" })));
+
+        let flushed = normalizer.flush().expect("pending tail should be flushed");
+        assert_eq!(delta_content(&flushed).as_deref(), Some("<th"));
+
+        // Nothing left to flush the second time.
+        assert!(normalizer.flush().is_none());
+    }
+
+    #[test]
+    fn flush_routes_pending_tail_to_reasoning_when_mid_think_block() {
+        let mut normalizer = StreamNormalizer::new();
+        normalizer.process_sse_chunk(&sse_chunk(json!({ "content": "<think>reasoning so far</thi" })));
+
+        let flushed = normalizer.flush().expect("pending tail should be flushed");
+        let s = std::str::from_utf8(&flushed).unwrap();
+        assert!(s.contains("\"reasoning_content\":\"</thi\""));
+        assert!(!s.contains("\"content\":\"</thi\""));
+    }
+}
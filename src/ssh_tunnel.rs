@@ -0,0 +1,260 @@
+//! Auto-managed SSH local-port-forward used to reach a remote LM Studio instance.
+//!
+//! Spawns the system `ssh` binary with `-L <local>:localhost:<remote> -N`, waits for
+//! the forwarded port to come up, and keeps it alive for the lifetime of the proxy,
+//! respawning with backoff if the tunnel drops.
+
+use std::net::TcpStream;
+use std::process::Stdio;
+use std::time::Duration;
+
+use tokio::process::{Child, Command};
+use tokio::sync::watch;
+use tokio::time::{sleep, timeout};
+use tracing::{error, info, warn};
+
+use crate::ServeConfig;
+
+/// A live SSH tunnel forwarding `127.0.0.1:<local_port>` to the remote LM Studio port.
+/// Dropping this does not tear the tunnel down by itself; call [`SshTunnel::shutdown`].
+pub struct SshTunnel {
+    pub local_port: u16,
+    shutdown_tx: watch::Sender<bool>,
+    supervisor: tokio::task::JoinHandle<()>,
+}
+
+impl SshTunnel {
+    /// Signals the supervisor to stop respawning and kills the current `ssh` child.
+    pub async fn shutdown(self) {
+        let _ = self.shutdown_tx.send(true);
+        let _ = self.supervisor.await;
+    }
+}
+
+/// If `config.ssh_host` is set, opens an SSH tunnel to the remote LM Studio instance
+/// and returns it; otherwise returns `Ok(None)` and the caller should use
+/// `config.lmstudio_url` unchanged.
+pub async fn spawn(config: &ServeConfig) -> Result<Option<SshTunnel>, Box<dyn std::error::Error>> {
+    let Some(ssh_host) = config.ssh_host.clone() else {
+        return Ok(None);
+    };
+
+    let local_port = pick_ephemeral_port()?;
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+
+    let spec = TunnelSpec {
+        ssh_host,
+        ssh_port: config.ssh_port,
+        ssh_key: config.ssh_key.clone(),
+        ssh_password: config.ssh_password.clone(),
+        remote_port: config.remote_lmstudio_port,
+        local_port,
+    };
+
+    let child = launch_ssh(&spec)?;
+    wait_for_port(local_port, Duration::from_secs(10)).await?;
+    info!(
+        "SSH tunnel up: 127.0.0.1:{} -> localhost:{} via {}",
+        local_port, spec.remote_port, spec.ssh_host
+    );
+
+    let supervisor = tokio::spawn(supervise(child, spec, shutdown_rx));
+
+    Ok(Some(SshTunnel {
+        local_port,
+        shutdown_tx,
+        supervisor,
+    }))
+}
+
+struct TunnelSpec {
+    ssh_host: String,
+    ssh_port: u16,
+    ssh_key: Option<String>,
+    ssh_password: Option<String>,
+    remote_port: u16,
+    local_port: u16,
+}
+
+fn pick_ephemeral_port() -> Result<u16, Box<dyn std::error::Error>> {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+    let port = listener.local_addr()?.port();
+    drop(listener);
+    Ok(port)
+}
+
+/// Builds the `ssh` argument list for a tunnel spec (the `sshpass -e ssh` prefix, if
+/// any, is applied separately when constructing the `Command`). Split out from
+/// [`launch_ssh`] so the argument shape can be tested without spawning a process.
+fn ssh_args(spec: &TunnelSpec) -> Vec<String> {
+    let forward = format!("{}:localhost:{}", spec.local_port, spec.remote_port);
+
+    let mut args = vec![
+        "-N".to_string(),
+        "-L".to_string(),
+        forward,
+        "-p".to_string(),
+        spec.ssh_port.to_string(),
+        "-o".to_string(),
+        "ExitOnForwardFailure=yes".to_string(),
+        "-o".to_string(),
+        "ServerAliveInterval=15".to_string(),
+    ];
+
+    if let Some(key) = &spec.ssh_key {
+        args.push("-i".to_string());
+        args.push(key.clone());
+    }
+
+    args.push(spec.ssh_host.clone());
+    args
+}
+
+fn launch_ssh(spec: &TunnelSpec) -> Result<Child, Box<dyn std::error::Error>> {
+    let mut command = if spec.ssh_password.is_some() {
+        let mut c = Command::new("sshpass");
+        c.arg("-e"); // reads the password from the SSHPASS env var, never argv
+        c.arg("ssh");
+        c
+    } else {
+        Command::new("ssh")
+    };
+
+    command.args(ssh_args(spec));
+
+    if let Some(password) = &spec.ssh_password {
+        command.env("SSHPASS", password);
+    }
+
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+
+    Ok(command.spawn()?)
+}
+
+async fn wait_for_port(port: u16, max_wait: Duration) -> Result<(), Box<dyn std::error::Error>> {
+    let deadline = tokio::time::Instant::now() + max_wait;
+    loop {
+        if TcpStream::connect(("127.0.0.1", port)).is_ok() {
+            return Ok(());
+        }
+        if tokio::time::Instant::now() >= deadline {
+            return Err(format!("SSH tunnel did not come up on port {} within {:?}", port, max_wait).into());
+        }
+        sleep(Duration::from_millis(200)).await;
+    }
+}
+
+async fn supervise(mut child: Child, spec: TunnelSpec, mut shutdown_rx: watch::Receiver<bool>) {
+    let mut backoff = Duration::from_secs(1);
+    const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+    loop {
+        tokio::select! {
+            _ = shutdown_rx.changed() => {
+                if *shutdown_rx.borrow() {
+                    let _ = child.start_kill();
+                    let _ = child.wait().await;
+                    return;
+                }
+            }
+            status = child.wait() => {
+                if *shutdown_rx.borrow() {
+                    return;
+                }
+                match status {
+                    Ok(status) => warn!("SSH tunnel to {} exited ({}), reconnecting in {:?}", spec.ssh_host, status, backoff),
+                    Err(e) => error!("SSH tunnel to {} failed ({}), reconnecting in {:?}", spec.ssh_host, e, backoff),
+                }
+
+                if timeout(backoff, shutdown_until_true(&mut shutdown_rx)).await.is_ok() {
+                    let _ = child.wait().await;
+                    return;
+                }
+
+                match launch_ssh(&spec) {
+                    Ok(new_child) => {
+                        child = new_child;
+                        backoff = Duration::from_secs(1);
+                    }
+                    Err(e) => {
+                        error!("Failed to respawn SSH tunnel to {}: {}", spec.ssh_host, e);
+                        backoff = (backoff * 2).min(MAX_BACKOFF);
+                    }
+                }
+            }
+        }
+    }
+}
+
+async fn shutdown_until_true(rx: &mut watch::Receiver<bool>) {
+    loop {
+        if *rx.borrow() {
+            return;
+        }
+        if rx.changed().await.is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(ssh_key: Option<&str>, ssh_password: Option<&str>) -> TunnelSpec {
+        TunnelSpec {
+            ssh_host: "user@example.com".to_string(),
+            ssh_port: 22,
+            ssh_key: ssh_key.map(str::to_string),
+            ssh_password: ssh_password.map(str::to_string),
+            remote_port: 1234,
+            local_port: 54321,
+        }
+    }
+
+    #[test]
+    fn ssh_args_forwards_local_to_remote_port() {
+        let args = ssh_args(&spec(None, None));
+        assert_eq!(args[0], "-N");
+        assert_eq!(args[1], "-L");
+        assert_eq!(args[2], "54321:localhost:1234");
+        assert_eq!(args.last().unwrap(), "user@example.com");
+    }
+
+    #[test]
+    fn ssh_args_includes_identity_file_when_key_given() {
+        let args = ssh_args(&spec(Some("/home/user/.ssh/id_ed25519"), None));
+        let i_pos = args.iter().position(|a| a == "-i").expect("-i flag present");
+        assert_eq!(args[i_pos + 1], "/home/user/.ssh/id_ed25519");
+    }
+
+    #[test]
+    fn ssh_args_omits_identity_file_when_no_key_given() {
+        let args = ssh_args(&spec(None, None));
+        assert!(!args.iter().any(|a| a == "-i"));
+    }
+
+    #[test]
+    fn pick_ephemeral_port_returns_a_bindable_port() {
+        let port = pick_ephemeral_port().unwrap();
+        assert!(port > 0);
+        assert!(std::net::TcpListener::bind(("127.0.0.1", port)).is_ok());
+    }
+
+    #[tokio::test]
+    async fn shutdown_until_true_returns_once_signaled() {
+        let (tx, mut rx) = watch::channel(false);
+        let waiter = tokio::spawn(async move {
+            shutdown_until_true(&mut rx).await;
+        });
+        tx.send(true).unwrap();
+        tokio::time::timeout(Duration::from_secs(1), waiter)
+            .await
+            .expect("shutdown_until_true should return promptly")
+            .unwrap();
+    }
+}